@@ -0,0 +1,186 @@
+//! Accumulates ground-intersection points from every track into a
+//! time-decaying density grid, so that stale activity fades out instead of
+//! vanishing the instant a track is drawn.
+
+use crate::renderer::{Color, Renderer};
+use crate::timestamp::parse_unix_secs;
+use crate::Point2D;
+
+/// Side length of the (square) accumulation grid, in cells.
+const GRID_SIZE: usize = 64;
+/// Time, in seconds, for a cell's weight to decay to `1/e` of its value.
+const HALF_LIFE_SECS: f64 = 30.0;
+/// Radius, in cells, of the Gaussian splat added per observation.
+const SPLAT_RADIUS_CELLS: f64 = 2.0;
+/// Cells below this weight are treated as empty and skipped on render.
+const EPSILON: f64 = 1e-3;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cell {
+    weight: f64,
+    last_update: f64,
+}
+
+/// A time-decaying, spatially-binned accumulation of ground-intersection
+/// points in normalized `[0, 1]^2` coordinates, as guaranteed by
+/// [`GroundProjector::project`](crate::projection::GroundProjector::project).
+pub struct Heatmap {
+    cells: Vec<Cell>,
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            cells: vec![Cell::default(); GRID_SIZE * GRID_SIZE],
+        }
+    }
+
+    /// Adds a Gaussian splat centered on `point` at time `timestamp`.
+    ///
+    /// `point` is expected to already be normalized `[0, 1]^2`; it's clamped
+    /// defensively in case a caller ever constructs one directly rather than
+    /// through [`GroundProjector::project`](crate::projection::GroundProjector::project).
+    ///
+    /// `timestamp` should be an RFC 3339 string as found in `Observation.timestamp`;
+    /// unparsable timestamps are ignored.
+    pub fn observe(&mut self, point: &Point2D, timestamp: &str) {
+        let Some(now) = parse_unix_secs(timestamp) else {
+            return;
+        };
+
+        let cx = (point.x.clamp(0.0, 1.0) as f64) * (GRID_SIZE - 1) as f64;
+        let cy = (point.y.clamp(0.0, 1.0) as f64) * (GRID_SIZE - 1) as f64;
+
+        let x0 = (cx - SPLAT_RADIUS_CELLS).floor().max(0.0) as usize;
+        let x1 = (cx + SPLAT_RADIUS_CELLS).ceil().min((GRID_SIZE - 1) as f64) as usize;
+        let y0 = (cy - SPLAT_RADIUS_CELLS).floor().max(0.0) as usize;
+        let y1 = (cy + SPLAT_RADIUS_CELLS).ceil().min((GRID_SIZE - 1) as f64) as usize;
+
+        for gy in y0..=y1 {
+            for gx in x0..=x1 {
+                let dx = gx as f64 - cx;
+                let dy = gy as f64 - cy;
+                let falloff = (-(dx * dx + dy * dy) / (2.0 * SPLAT_RADIUS_CELLS * SPLAT_RADIUS_CELLS)).exp();
+                if falloff < EPSILON {
+                    continue;
+                }
+                let idx = gy * GRID_SIZE + gx;
+                self.decay_cell(idx, now);
+                self.cells[idx].weight += falloff;
+                // Same monotonic guard as `decay_cell`: a later-finishing
+                // track can replay an earlier timestamp into this cell, and
+                // that must not rewind `last_update` either.
+                self.cells[idx].last_update = self.cells[idx].last_update.max(now);
+            }
+        }
+    }
+
+    /// Decays every cell to `now`, clearing those that have faded below [`EPSILON`].
+    pub fn decay_to(&mut self, now: f64) {
+        for idx in 0..self.cells.len() {
+            self.decay_cell(idx, now);
+        }
+    }
+
+    /// Decays a single cell to `now`, a timestamp that is not guaranteed to
+    /// be monotonic across calls: tracks are only replayed into `observe()`
+    /// once they've finished, and finish order is uncorrelated with the
+    /// timestamp range a track carries, so a cell can see a later-finishing
+    /// track replay *older* observation timestamps than one that finished
+    /// sooner. `last_update` is therefore only ever advanced forward — never
+    /// rewound — so a later, earlier-timestamped replay can't fabricate an
+    /// inflated `dt` for the next forward-moving decay.
+    fn decay_cell(&mut self, idx: usize, now: f64) {
+        let cell = &mut self.cells[idx];
+        if now <= cell.last_update {
+            return;
+        }
+        if cell.weight != 0.0 {
+            let dt = now - cell.last_update;
+            cell.weight *= (-dt / HALF_LIFE_SECS).exp();
+            if cell.weight < EPSILON {
+                cell.weight = 0.0;
+            }
+        }
+        cell.last_update = now;
+    }
+
+    /// Renders every non-empty cell as a small filled square, colored by a
+    /// blue (cold) to red (hot) ramp.
+    pub fn render(&self, renderer: &mut dyn Renderer) -> anyhow::Result<()> {
+        let cell_size = 1.0 / GRID_SIZE as f32;
+        for (idx, cell) in self.cells.iter().enumerate() {
+            if cell.weight < EPSILON {
+                continue;
+            }
+            let gx = (idx % GRID_SIZE) as f32;
+            let gy = (idx / GRID_SIZE) as f32;
+            let x0 = gx * cell_size;
+            let y0 = gy * cell_size;
+            let x1 = x0 + cell_size;
+            let y1 = y0 + cell_size;
+
+            renderer.fill_rect(x0, y0, x1, y1, color_ramp(cell.weight))?;
+        }
+        Ok(())
+    }
+}
+
+/// Maps an (unbounded, but typically < 1) weight to a blue-to-red color ramp.
+fn color_ramp(weight: f64) -> Color {
+    let t = weight.clamp(0.0, 1.0);
+    let r = (t * 255.0).round() as u8;
+    let b = ((1.0 - t) * 255.0).round() as u8;
+    Color::from_rgb(r, 0, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn observation_fades_after_half_life() {
+        let mut heatmap = Heatmap::new();
+        let point = Point2D { x: 0.5, y: 0.5 };
+        heatmap.observe(&point, "1970-01-01T00:00:00Z");
+        let idx = (GRID_SIZE / 2) * GRID_SIZE + GRID_SIZE / 2;
+        let weight_at_splat = heatmap.cells[idx].weight;
+        assert!(weight_at_splat > 0.0);
+
+        heatmap.decay_to(HALF_LIFE_SECS);
+        let decayed = heatmap.cells[idx].weight;
+        assert!((decayed - weight_at_splat / std::f64::consts::E).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stale_cells_clear_to_zero() {
+        let mut heatmap = Heatmap::new();
+        let point = Point2D { x: 0.5, y: 0.5 };
+        heatmap.observe(&point, "1970-01-01T00:00:00Z");
+        heatmap.decay_to(HALF_LIFE_SECS * 100.0);
+        assert!(heatmap.cells.iter().all(|c| c.weight == 0.0));
+    }
+
+    #[test]
+    fn a_later_finishing_track_with_earlier_timestamps_does_not_rewind_decay() {
+        // Tracks are only replayed once finished, and finish order is
+        // uncorrelated with the timestamp range a track carries: a short
+        // track that finishes quickly can apply recent timestamps to a cell
+        // before a long-running track finishes later and replays much
+        // older observation timestamps into the same cell.
+        let mut heatmap = Heatmap::new();
+        let point = Point2D { x: 0.5, y: 0.5 };
+        let idx = (GRID_SIZE / 2) * GRID_SIZE + GRID_SIZE / 2;
+
+        // The short track finishes first, observed at a recent time...
+        heatmap.observe(&point, "1970-01-01T00:01:00Z");
+        heatmap.decay_to(60.0);
+        let weight_after_short_track = heatmap.cells[idx].weight;
+
+        // ...then the long-running track finishes and replays an earlier
+        // observation into the same cell. `last_update` must not rewind.
+        heatmap.observe(&point, "1970-01-01T00:00:00Z");
+        assert_eq!(heatmap.cells[idx].last_update, 60.0);
+        assert!(heatmap.cells[idx].weight >= weight_after_short_track);
+    }
+}
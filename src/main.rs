@@ -1,22 +1,44 @@
+mod heatmap;
+mod projection;
+mod renderer;
+mod schema;
+mod subscription;
+mod timestamp;
+
 use std::ffi::CStr;
+use std::time::{Duration, Instant};
 
-use bbox::flex::{Bbox, Color as BboxColor};
 use log::{debug, warn};
-use mdb::{Connection, Subscriber, SubscriberConfig};
+use mdb::Connection;
 use serde::{Deserialize, Serialize};
 
+use heatmap::Heatmap;
+use projection::GroundProjector;
+use renderer::{Color as RenderColor, Config as RendererConfig, Renderer};
+use subscription::{Handler, SubscriptionManager};
+
 const TOPIC: &CStr = c"com.axis.consolidated_track.v1.beta";
 const SOURCE: &CStr = c"1";
 
+const SCENE_METADATA_TOPIC: &CStr = c"com.axis.scene_metadata.v1.beta";
+const SCENE_METADATA_SOURCE: &CStr = c"1";
+
 const SENSITIVITY: f64 = 190.0;
 
-#[derive(Debug)]
-struct Point2D {
-    x: f32,
-    y: f32,
+/// Minimum wall-clock time between heatmap redraws. Redrawing is a full pass
+/// over every non-empty cell through `Renderer::fill_rect`, so doing it on
+/// every single handled track-end message can turn one event into hundreds
+/// of overlay calls; throttling it to real elapsed time decouples overlay
+/// load from track-end frequency.
+const HEATMAP_RENDER_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Point2D {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
 }
 #[derive(Serialize, Deserialize, Debug)]
-struct BoundingBox {
+pub(crate) struct BoundingBox {
     top: f32,
     left: f32,
     right: f32,
@@ -33,19 +55,19 @@ impl BoundingBox {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Observation {
+pub(crate) struct Observation {
     bounding_box: BoundingBox,
     timestamp: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Color {
+pub(crate) struct Color {
     name: String,
     score: f32,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Class {
+pub(crate) struct Class {
     colors: Vec<Color>,
     score: f32,
     #[serde(rename = "type")]
@@ -53,7 +75,7 @@ struct Class {
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-enum ClassType {
+pub(crate) enum ClassType {
     Bike,
     Bus,
     Car,
@@ -62,67 +84,60 @@ enum ClassType {
     Vehicle,
 }
 #[derive(Serialize, Deserialize, Debug)]
-struct Data {
+pub(crate) struct Data {
     #[serde(default = "Vec::new")]
-    classes: Vec<Class>,
-    duration: f32,
-    end_time: Option<String>,
-    id: String,
-    observations: Vec<Observation>,
-    start_time: String,
+    pub(crate) classes: Vec<Class>,
+    pub(crate) duration: f32,
+    pub(crate) end_time: Option<String>,
+    pub(crate) id: String,
+    pub(crate) observations: Vec<Observation>,
+    pub(crate) start_time: String,
 }
 
-fn main() -> anyhow::Result<()> {
-    acap_logging::init_logger();
+/// Decodes `consolidated_track.v1.beta` payloads and draws finished tracks,
+/// accumulating their ground-intersection points into a heatmap.
+struct TrackHandler {
+    renderer: Box<dyn Renderer>,
+    projector: GroundProjector,
+    heatmap: Heatmap,
+    last_heatmap_render: Instant,
+    gold: RenderColor,
+    orange: RenderColor,
+    blue: RenderColor,
+    green: RenderColor,
+    red: RenderColor,
+    gray: RenderColor,
+}
 
-    let (tx, rx) = std::sync::mpsc::sync_channel(1);
-    let mut droppable_tx = Some(tx);
+impl TrackHandler {
+    fn new(renderer: Box<dyn Renderer>, projector: GroundProjector) -> Self {
+        Self {
+            renderer,
+            projector,
+            heatmap: Heatmap::new(),
+            // Subtracting the interval, rather than starting at `now()`,
+            // lets the very first track-end message render immediately.
+            last_heatmap_render: Instant::now() - HEATMAP_RENDER_INTERVAL,
+            gold: RenderColor::from_rgb(0xFF, 0xD7, 0x00),
+            orange: RenderColor::from_rgb(0xFF, 0x8C, 0x00),
+            blue: RenderColor::from_rgb(0x00, 0x00, 0xFF),
+            green: RenderColor::from_rgb(0x32, 0xCD, 0x32),
+            red: RenderColor::from_rgb(0x8B, 0x00, 0x00),
+            gray: RenderColor::from_rgb(0x80, 0x80, 0x80),
+        }
+    }
+}
 
-    let connection =
-        Connection::try_new(Some(Box::new(|e| warn!("Not connected because {e:?}")))).unwrap();
-    let config = SubscriberConfig::try_new(
-        TOPIC,
-        SOURCE,
-        Box::new(move |message| {
-            let payload = String::from_utf8(message.payload().to_vec());
-            let Some(tx) = &droppable_tx else {
-                debug!("Dropping message because sender was previously dropped");
-                return;
-            };
-            if tx.try_send(payload).is_err() {
-                warn!("Dropping sender because receiver has been deallocated");
-                droppable_tx = None;
-            }
-        }),
-    )
-    .unwrap();
-    let _subscriber = Subscriber::try_new(
-        &connection,
-        config,
-        Box::new(|e| match e {
-            None => debug!("Subscribed"),
-            Some(e) => warn!("Not subscribed because {e:?}"),
-        }),
-    )
-    .unwrap();
-
-    let mut bbox = Bbox::try_view_new(1)?;
-    let gold = BboxColor::from_rgb(0xFF, 0xD7, 0x00);
-    let orange = BboxColor::from_rgb(0xFF, 0x8C, 0x00);
-    let blue = BboxColor::from_rgb(0x00, 0x00, 0xFF);
-    let green = BboxColor::from_rgb(0x32, 0xCD, 0x32);
-    let red = BboxColor::from_rgb(0x8B, 0x00, 0x00);
-    let gray = BboxColor::from_rgb(0x80, 0x80, 0x80);
-
-    bbox.try_clear()?;
-    while let Ok(msg) = rx.recv() {
-        let msg = msg?;
-        let msg = match serde_json::from_str(&msg) {
+impl Handler for TrackHandler {
+    fn handle(&mut self, payload: &str) -> anyhow::Result<()> {
+        let msg: Data = match schema::decode(payload) {
             Ok(d) => d,
             Err(e) => {
-                debug!("Received {msg:?}");
-                warn!("Could not deserialize because {e:?}");
-                continue;
+                if e.downcast_ref::<schema::UnsupportedVersion>().is_none() {
+                    debug!("Received {payload:?}");
+                    warn!("Could not deserialize because {e:?}");
+                }
+                return Ok(());
             }
         };
         let Data {
@@ -133,39 +148,139 @@ fn main() -> anyhow::Result<()> {
         } = msg;
         if end_time.is_none() {
             debug!("Track has not ended, skipping.");
-            continue;
+            return Ok(());
         }
         let Some(class) = classes.first() else {
             warn!("No classes, skipping");
-            continue;
+            return Ok(());
         };
 
         let color = match class.class_type {
-            ClassType::Bike => gold,
-            ClassType::Bus => orange,
-            ClassType::Car => blue,
-            ClassType::Human => green,
-            ClassType::Truck => red,
-            ClassType::Vehicle => gray,
+            ClassType::Bike => self.gold,
+            ClassType::Bus => self.orange,
+            ClassType::Car => self.blue,
+            ClassType::Human => self.green,
+            ClassType::Truck => self.red,
+            ClassType::Vehicle => self.gray,
         };
 
-        // The program sometimes exits because one of the bbox calls fail.
-        // Not sure which, why or what to do though.
-        bbox.try_color(color)?;
+        for obs in &observations {
+            let ground = self.projector.project(obs.bounding_box.ground_intersection());
+            self.heatmap.observe(&ground, &obs.timestamp);
+        }
+
+        self.renderer.begin_track(color)?;
         let step = (observations.len() as f64 / SENSITIVITY).ceil().max(1.0) as usize;
-        let mut observations = observations.into_iter().step_by(step);
-        if let Some(obs) = observations.next() {
-            let Point2D { x, y } = obs.bounding_box.ground_intersection();
-            bbox.try_move_to(x, y)?;
+        for obs in observations.iter().step_by(step) {
+            let Point2D { x, y } = self.projector.project(obs.bounding_box.ground_intersection());
+            self.renderer.point(x, y)?;
         }
-        for obs in observations {
-            let Point2D { x, y } = obs.bounding_box.ground_intersection();
-            // On at least one occasion this failed:
-            // Protocol not available (os error 92)
-            bbox.try_line_to(x, y)?;
+        self.renderer.stroke()?;
+
+        if let Some(now) = timestamp::parse_unix_secs(&end_time.unwrap()) {
+            self.heatmap.decay_to(now);
         }
-        bbox.try_draw_path()?;
-        bbox.try_commit(0)?;
+        if self.last_heatmap_render.elapsed() >= HEATMAP_RENDER_INTERVAL {
+            self.heatmap.render(self.renderer.as_mut())?;
+            self.last_heatmap_render = Instant::now();
+        }
+
+        self.renderer.commit()?;
+        Ok(())
+    }
+}
+
+/// Logs `scene_metadata` payloads. A minimal second consumer, proving that
+/// [`SubscriptionManager`] really dispatches by topic rather than just
+/// routing everything through to a single hardcoded handler.
+struct SceneMetadataHandler;
+
+impl Handler for SceneMetadataHandler {
+    fn handle(&mut self, payload: &str) -> anyhow::Result<()> {
+        debug!("Received scene metadata: {payload}");
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    acap_logging::init_logger();
+
+    let connection =
+        Connection::try_new(Some(Box::new(|e| warn!("Not connected because {e:?}")))).unwrap();
+
+    let renderer = renderer_config_from_env().build()?;
+    let projector = ground_projector_from_env();
+    let mut manager = SubscriptionManager::new(&connection);
+    manager.register_coalescing(
+        TOPIC,
+        SOURCE,
+        Box::new(TrackHandler::new(renderer, projector)),
+        Some(Box::new(track_id_key)),
+    )?;
+    manager.register(
+        SCENE_METADATA_TOPIC,
+        SCENE_METADATA_SOURCE,
+        Box::new(SceneMetadataHandler),
+    )?;
+    manager.run()
+}
+
+/// Builds a [`GroundProjector`] from the environment, defaulting to
+/// [`GroundProjector::Identity`] (today's naive bottom-center behavior). Set
+/// `ACAP_BBOX_HEATMAP_HOMOGRAPHY` to 9 comma-separated, row-major matrix
+/// entries to calibrate a perspective-corrected ground plane instead.
+fn ground_projector_from_env() -> GroundProjector {
+    let Ok(raw) = std::env::var("ACAP_BBOX_HEATMAP_HOMOGRAPHY") else {
+        return GroundProjector::Identity;
+    };
+    let entries: Result<Vec<f64>, _> = raw.split(',').map(|entry| entry.trim().parse::<f64>()).collect();
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("ACAP_BBOX_HEATMAP_HOMOGRAPHY contained a non-numeric entry ({e}), ignoring it");
+            return GroundProjector::Identity;
+        }
+    };
+    let [a, b, c, d, e, f, g, h, i]: [f64; 9] = match entries.try_into() {
+        Ok(entries) => entries,
+        Err(entries) => {
+            warn!(
+                "ACAP_BBOX_HEATMAP_HOMOGRAPHY had {} number(s), expected 9, ignoring it",
+                entries.len()
+            );
+            return GroundProjector::Identity;
+        }
+    };
+    match projection::Homography::try_new([[a, b, c], [d, e, f], [g, h, i]]) {
+        Some(homography) => GroundProjector::Homography(homography),
+        None => {
+            warn!("ACAP_BBOX_HEATMAP_HOMOGRAPHY is singular (not invertible), ignoring it");
+            GroundProjector::Identity
+        }
+    }
+}
+
+/// Extracts the `id` field from a raw `consolidated_track` payload, used to
+/// coalesce queued updates for the same track rather than shedding them.
+fn track_id_key(payload: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(payload)
+        .ok()?
+        .get("id")?
+        .as_str()
+        .map(String::from)
+}
+
+/// Picks a [`RendererConfig`] from the environment, defaulting to the
+/// on-camera bbox overlay. Set `ACAP_BBOX_HEATMAP_RENDERER=framebuffer` to
+/// instead dump frames to `/tmp/acap-bbox-heatmap.ppm`, e.g. for headless
+/// testing.
+fn renderer_config_from_env() -> RendererConfig {
+    match std::env::var("ACAP_BBOX_HEATMAP_RENDERER").as_deref() {
+        Ok("framebuffer") => RendererConfig::FrameBuffer {
+            width: 640,
+            height: 480,
+            path: "/tmp/acap-bbox-heatmap.ppm".into(),
+        },
+        _ => RendererConfig::Bbox { view: 1 },
     }
-    Ok(())
 }
@@ -0,0 +1,177 @@
+//! Configurable projection from image-plane points to a ground plane.
+//!
+//! [`BoundingBox::ground_intersection`] approximates ground contact as the
+//! horizontal center of the box at its bottom edge, which distorts both the
+//! drawn path and the heatmap under perspective. A calibrated [`Homography`]
+//! corrects for that; [`GroundProjector::Identity`] keeps today's behavior
+//! when no calibration is configured.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::warn;
+
+use crate::Point2D;
+
+/// Below this, a homography's denominator `w'` is treated as zero: the
+/// point is at or behind the plane's vanishing line and has no finite
+/// ground position.
+const WP_EPSILON: f64 = 1e-9;
+/// Below this, a homography's determinant is treated as zero: the matrix is
+/// singular (not invertible) and can't be a valid perspective transform.
+const DETERMINANT_EPSILON: f64 = 1e-9;
+
+static DEGENERATE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Maps an image-plane point to a point on the ground plane.
+pub enum GroundProjector {
+    /// Passthrough: the image-plane point is used as-is.
+    Identity,
+    /// A calibrated perspective correction.
+    Homography(Homography),
+}
+
+impl GroundProjector {
+    /// Maps `point` to the ground plane, guaranteeing the result lands in
+    /// normalized `[0, 1]^2`, as [`Heatmap`](crate::heatmap::Heatmap) and
+    /// [`Renderer`](crate::renderer::Renderer) both expect of their input.
+    ///
+    /// If the homography is degenerate for this point (denominator near
+    /// zero, a non-finite result) or simply projects outside that range,
+    /// this falls back to the original, un-projected point rather than
+    /// passing a nonsensical one on; falling back is logged with
+    /// decreasing frequency as it accumulates.
+    pub fn project(&self, point: Point2D) -> Point2D {
+        let projected = match self {
+            GroundProjector::Identity => point,
+            GroundProjector::Homography(homography) => match homography.apply(point) {
+                Some(p) if (0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.y) => p,
+                _ => {
+                    let count = DEGENERATE_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+                    if count.is_power_of_two() {
+                        warn!(
+                            "Rejected {count} degenerate or out-of-range homography projection(s) so far, falling back to the unprojected point"
+                        );
+                    }
+                    point
+                }
+            },
+        };
+        Point2D {
+            x: projected.x.clamp(0.0, 1.0),
+            y: projected.y.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// A 3x3 homography mapping image-plane points to a top-down ground plane:
+/// `[x', y', w'] = H * [x, y, 1]`, then `(x'/w', y'/w')`.
+#[derive(Debug, Clone, Copy)]
+pub struct Homography([[f64; 3]; 3]);
+
+impl Homography {
+    /// Builds a homography from `matrix`, rejecting it if it's singular (or
+    /// too close to it to trust): such a matrix has no consistent inverse,
+    /// so `apply` would be dividing by an arbitrarily small number for
+    /// every point rather than a meaningful perspective correction.
+    ///
+    /// A homography is only defined up to scale, so the determinant is
+    /// checked against `matrix` normalized by its largest entry rather than
+    /// `matrix` directly: otherwise an equally valid matrix scaled down for,
+    /// say, numerical conditioning would shrink the raw determinant by the
+    /// cube of that scale and get rejected as singular when it isn't.
+    pub fn try_new(matrix: [[f64; 3]; 3]) -> Option<Self> {
+        let scale = matrix.iter().flatten().fold(0.0_f64, |max, v| max.max(v.abs()));
+        if scale == 0.0 || !scale.is_finite() {
+            return None;
+        }
+        let normalized = matrix.map(|row| row.map(|v| v / scale));
+        let det = determinant3(&normalized);
+        if !det.is_finite() || det.abs() < DETERMINANT_EPSILON {
+            return None;
+        }
+        Some(Self(matrix))
+    }
+
+    /// Applies the homography, returning `None` if the result is degenerate
+    /// (`w'` too close to zero) or not finite.
+    fn apply(&self, point: Point2D) -> Option<Point2D> {
+        let (x, y) = (point.x as f64, point.y as f64);
+        let h = &self.0;
+        let xp = h[0][0] * x + h[0][1] * y + h[0][2];
+        let yp = h[1][0] * x + h[1][1] * y + h[1][2];
+        let wp = h[2][0] * x + h[2][1] * y + h[2][2];
+        if wp.abs() < WP_EPSILON {
+            return None;
+        }
+        let (x, y) = ((xp / wp) as f32, (yp / wp) as f32);
+        if !x.is_finite() || !y.is_finite() {
+            return None;
+        }
+        Some(Point2D { x, y })
+    }
+}
+
+fn determinant3(m: &[[f64; 3]; 3]) -> f64 {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_matrix_is_a_passthrough() {
+        let homography = Homography::try_new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]).unwrap();
+        let projector = GroundProjector::Homography(homography);
+        let point = Point2D { x: 0.3, y: 0.8 };
+        let projected = projector.project(Point2D { x: 0.3, y: 0.8 });
+        assert_eq!(projected.x, point.x);
+        assert_eq!(projected.y, point.y);
+    }
+
+    #[test]
+    fn scaling_matrix_scales_both_axes() {
+        let homography = Homography::try_new([[2.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 1.0]]).unwrap();
+        let projector = GroundProjector::Homography(homography);
+        let projected = projector.project(Point2D { x: 0.25, y: 0.25 });
+        assert_eq!(projected.x, 0.5);
+        assert_eq!(projected.y, 0.5);
+    }
+
+    #[test]
+    fn singular_matrix_is_rejected_at_construction() {
+        // All-zero last row makes every `w'` zero, so this can never invert.
+        assert!(Homography::try_new([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]]).is_none());
+    }
+
+    #[test]
+    fn uniformly_scaled_down_matrix_is_still_accepted() {
+        // A homography is only defined up to scale: shrinking every entry by
+        // the same factor represents the exact same transform and must not
+        // be rejected just because its raw determinant shrank too.
+        let tiny = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]].map(|row| row.map(|v| v * 1e-6));
+        assert!(Homography::try_new(tiny).is_some());
+    }
+
+    #[test]
+    fn out_of_range_projection_falls_back_to_the_unprojected_point() {
+        // Scales far past [0, 1] for this point, so it should fall back
+        // rather than hand the heatmap/renderer an out-of-range coordinate.
+        let homography = Homography::try_new([[10.0, 0.0, 0.0], [0.0, 10.0, 0.0], [0.0, 0.0, 1.0]]).unwrap();
+        let projector = GroundProjector::Homography(homography);
+        let point = Point2D { x: 0.5, y: 0.5 };
+        let projected = projector.project(Point2D { x: 0.5, y: 0.5 });
+        assert_eq!(projected.x, point.x);
+        assert_eq!(projected.y, point.y);
+    }
+
+    #[test]
+    fn project_always_returns_normalized_output() {
+        let homography = Homography::try_new([[1.0, 0.0, 0.3], [0.0, 1.0, 0.3], [0.0, 0.0, 1.0]]).unwrap();
+        let projector = GroundProjector::Homography(homography);
+        let projected = projector.project(Point2D { x: 0.9, y: 0.9 });
+        assert!((0.0..=1.0).contains(&projected.x));
+        assert!((0.0..=1.0).contains(&projected.y));
+    }
+}
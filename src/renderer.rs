@@ -0,0 +1,278 @@
+//! Output backends for drawn tracks and the accumulated heatmap.
+//!
+//! `main` no longer talks to `bbox::flex::Bbox` directly. Instead it drives a
+//! [`Renderer`], so a failure to reach the overlay service (e.g. the
+//! occasional `Protocol not available (os error 92)`) is something a single
+//! backend can recover from or report, rather than something that kills the
+//! whole process.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use bbox::flex::{Bbox, Color as BboxColor};
+
+/// An RGB color, independent of any particular backend's color type.
+#[derive(Debug, Clone, Copy)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl From<Color> for BboxColor {
+    fn from(color: Color) -> Self {
+        BboxColor::from_rgb(color.r, color.g, color.b)
+    }
+}
+
+/// A sink for drawn paths, in normalized `[0, 1]^2` coordinates, as
+/// guaranteed by [`GroundProjector::project`](crate::projection::GroundProjector::project)
+/// for projected points.
+///
+/// Usage is `begin_track` to pick a color, then one or more `point` calls,
+/// then `stroke` to flush the traced path. Further `point` calls after a
+/// `stroke` start a new path in the same color, as the default `fill_rect`
+/// does. `commit` flushes everything drawn so far to the backend's actual
+/// output (screen overlay, file, ...).
+pub trait Renderer {
+    /// Starts a new path in the given color.
+    fn begin_track(&mut self, color: Color) -> anyhow::Result<()>;
+    /// Extends the current path to `(x, y)`.
+    fn point(&mut self, x: f32, y: f32) -> anyhow::Result<()>;
+    /// Draws the path traced so far by `point`.
+    fn stroke(&mut self) -> anyhow::Result<()>;
+    /// Makes everything drawn since the last `commit` visible.
+    fn commit(&mut self) -> anyhow::Result<()>;
+
+    /// Fills the axis-aligned rectangle `(x0, y0)..(x1, y1)` solidly with
+    /// `color`. The default implementation approximates a fill as a dense
+    /// stack of horizontal strokes, since `begin_track`/`point`/`stroke`
+    /// above only trace outlines; backends that can fill natively (e.g. a
+    /// pixel buffer) should override this with an exact fill.
+    fn fill_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Color) -> anyhow::Result<()> {
+        const SCANLINES: u32 = 6;
+        self.begin_track(color)?;
+        for i in 0..=SCANLINES {
+            let y = y0 + (y1 - y0) * (i as f32 / SCANLINES as f32);
+            self.point(x0, y)?;
+            self.point(x1, y)?;
+            self.stroke()?;
+        }
+        Ok(())
+    }
+}
+
+/// Renders onto the on-camera bbox overlay.
+pub struct BboxRenderer {
+    bbox: Bbox,
+    started: bool,
+}
+
+impl BboxRenderer {
+    pub fn try_new(view: u32) -> anyhow::Result<Self> {
+        let mut bbox = Bbox::try_view_new(view)?;
+        bbox.try_clear()?;
+        Ok(Self {
+            bbox,
+            started: false,
+        })
+    }
+}
+
+impl Renderer for BboxRenderer {
+    fn begin_track(&mut self, color: Color) -> anyhow::Result<()> {
+        self.bbox.try_color(color.into())?;
+        self.started = false;
+        Ok(())
+    }
+
+    fn point(&mut self, x: f32, y: f32) -> anyhow::Result<()> {
+        // `point` is documented as taking normalized [0, 1]^2 coordinates,
+        // which `GroundProjector::project` now guarantees; clamp anyway so a
+        // point constructed some other way can't reach the overlay FFI
+        // out of range.
+        let (x, y) = (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0));
+        if self.started {
+            // On at least one occasion this failed:
+            // Protocol not available (os error 92)
+            self.bbox.try_line_to(x, y)?;
+        } else {
+            self.bbox.try_move_to(x, y)?;
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    fn stroke(&mut self) -> anyhow::Result<()> {
+        self.bbox.try_draw_path()?;
+        self.started = false;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        self.bbox.try_commit(0)?;
+        Ok(())
+    }
+}
+
+/// Renders into an in-memory RGB frame buffer and dumps it to a PPM file on
+/// every commit. Useful for headless testing and for exporting the
+/// accumulated heatmap without a camera overlay available.
+pub struct FrameBufferRenderer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
+    path: PathBuf,
+    color: Color,
+    last_point: Option<(f32, f32)>,
+}
+
+impl FrameBufferRenderer {
+    pub fn new(width: u32, height: u32, path: PathBuf) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::from_rgb(0, 0, 0); (width * height) as usize],
+            path,
+            color: Color::from_rgb(255, 255, 255),
+            last_point: None,
+        }
+    }
+
+    fn set_pixel(&mut self, x: i64, y: i64, color: Color) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let idx = y as usize * self.width as usize + x as usize;
+        self.pixels[idx] = color;
+    }
+
+    fn to_pixel(&self, x: f32, y: f32) -> (i64, i64) {
+        (
+            (x.clamp(0.0, 1.0) * (self.width - 1) as f32).round() as i64,
+            (y.clamp(0.0, 1.0) * (self.height - 1) as f32).round() as i64,
+        )
+    }
+
+    /// Draws a line between two points already in pixel space, via Bresenham's algorithm.
+    fn draw_line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), color: Color) {
+        let dx = (x1 - x0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let dy = -(y1 - y0).abs();
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+impl Renderer for FrameBufferRenderer {
+    fn begin_track(&mut self, color: Color) -> anyhow::Result<()> {
+        self.color = color;
+        self.last_point = None;
+        Ok(())
+    }
+
+    fn point(&mut self, x: f32, y: f32) -> anyhow::Result<()> {
+        let here = self.to_pixel(x, y);
+        if let Some(prev) = self.last_point {
+            self.draw_line(self.to_pixel(prev.0, prev.1), here, self.color);
+        } else {
+            self.set_pixel(here.0, here.1, self.color);
+        }
+        self.last_point = Some((x, y));
+        Ok(())
+    }
+
+    fn stroke(&mut self) -> anyhow::Result<()> {
+        self.last_point = None;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        let mut file = fs::File::create(&self.path)?;
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in &self.pixels {
+            file.write_all(&[pixel.r, pixel.g, pixel.b])?;
+        }
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Color) -> anyhow::Result<()> {
+        let (px0, py0) = self.to_pixel(x0, y0);
+        let (px1, py1) = self.to_pixel(x1, y1);
+        for y in py0.min(py1)..=py0.max(py1) {
+            for x in px0.min(px1)..=px0.max(px1) {
+                self.set_pixel(x, y, color);
+            }
+        }
+        self.last_point = None;
+        Ok(())
+    }
+}
+
+/// Selects which [`Renderer`] backend to build at startup.
+pub enum Config {
+    Bbox { view: u32 },
+    FrameBuffer { width: u32, height: u32, path: PathBuf },
+}
+
+impl Config {
+    pub fn build(self) -> anyhow::Result<Box<dyn Renderer>> {
+        match self {
+            Config::Bbox { view } => Ok(Box::new(BboxRenderer::try_new(view)?)),
+            Config::FrameBuffer { width, height, path } => {
+                Ok(Box::new(FrameBufferRenderer::new(width, height, path)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_buffer_draws_a_pixel_per_point() {
+        let mut renderer = FrameBufferRenderer::new(4, 4, PathBuf::from("/tmp/does-not-matter.ppm"));
+        renderer.begin_track(Color::from_rgb(1, 2, 3)).unwrap();
+        renderer.point(0.0, 0.0).unwrap();
+        renderer.stroke().unwrap();
+        let idx = 0;
+        assert_eq!(renderer.pixels[idx].r, 1);
+    }
+
+    #[test]
+    fn frame_buffer_fill_rect_covers_every_pixel_in_range() {
+        let mut renderer = FrameBufferRenderer::new(4, 4, PathBuf::from("/tmp/does-not-matter.ppm"));
+        renderer.fill_rect(0.0, 0.0, 0.5, 0.5, Color::from_rgb(9, 9, 9)).unwrap();
+        for y in 0..2 {
+            for x in 0..2 {
+                let idx = y * 4 + x;
+                assert_eq!(renderer.pixels[idx].r, 9, "pixel ({x}, {y}) was not filled");
+            }
+        }
+        assert_eq!(renderer.pixels[3 * 4 + 3].r, 0, "fill leaked outside requested range");
+    }
+}
@@ -0,0 +1,122 @@
+//! Schema-version negotiation for `consolidated_track` payloads.
+//!
+//! The topic name is already versioned
+//! (`com.axis.consolidated_track.v1.beta`), but that doesn't stop firmware
+//! from changing the payload shape within it. Rather than let every shape
+//! mismatch surface as the same generic "could not deserialize" warning,
+//! pull a version discriminator out first: known versions (currently `"1"`
+//! and `"2"`) are decoded with their own shape and converted into [`Data`],
+//! and anything else gets its own distinct, rate-limited warning instead.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::{Class, Data, Observation};
+
+/// Payloads that predate any `schema_version` field are treated as this version.
+const DEFAULT_VERSION: &str = "1";
+
+static UNSUPPORTED_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// A payload declared a `schema_version` this build doesn't know how to decode.
+#[derive(Debug)]
+pub struct UnsupportedVersion(pub String);
+
+impl std::fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported consolidated_track schema version {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default = "default_version")]
+    schema_version: String,
+}
+
+fn default_version() -> String {
+    DEFAULT_VERSION.to_string()
+}
+
+/// Version 2 of the `consolidated_track` payload: identical to version 1
+/// except `start_time`/`end_time` were renamed to `started_at`/`ended_at`.
+#[derive(Deserialize)]
+struct DataV2 {
+    #[serde(default = "Vec::new")]
+    classes: Vec<Class>,
+    duration: f32,
+    ended_at: Option<String>,
+    id: String,
+    observations: Vec<Observation>,
+    started_at: String,
+}
+
+impl From<DataV2> for Data {
+    fn from(v2: DataV2) -> Self {
+        Data {
+            classes: v2.classes,
+            duration: v2.duration,
+            end_time: v2.ended_at,
+            id: v2.id,
+            observations: v2.observations,
+            start_time: v2.started_at,
+        }
+    }
+}
+
+/// Decodes a `consolidated_track` payload, checking its schema version
+/// before committing to a concrete shape.
+///
+/// Unsupported versions fail with [`UnsupportedVersion`], logged here with
+/// decreasing frequency as they accumulate so one firmware mismatch can't
+/// flood the log. Everything else is left for the caller to report, since a
+/// malformed payload at a supported version is a different, less common
+/// kind of problem.
+pub fn decode(payload: &str) -> anyhow::Result<Data> {
+    let probe: VersionProbe = serde_json::from_str(payload)?;
+    match probe.schema_version.as_str() {
+        DEFAULT_VERSION => Ok(serde_json::from_str::<Data>(payload)?),
+        "2" => Ok(serde_json::from_str::<DataV2>(payload)?.into()),
+        _ => {
+            let count = UNSUPPORTED_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+            if count.is_power_of_two() {
+                warn!(
+                    "Rejected {count} payload(s) so far with an unsupported schema version, latest {:?}",
+                    probe.schema_version
+                );
+            }
+            Err(UnsupportedVersion(probe.schema_version).into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn v1_and_v2_payloads_decode_to_equivalent_data() {
+        let v1 = decode(
+            r#"{"schema_version":"1","duration":1.0,"start_time":"2024-01-01T00:00:00Z","end_time":"2024-01-01T00:00:01Z","id":"a","observations":[]}"#,
+        )
+        .unwrap();
+        let v2 = decode(
+            r#"{"schema_version":"2","duration":1.0,"started_at":"2024-01-01T00:00:00Z","ended_at":"2024-01-01T00:00:01Z","id":"a","observations":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(v1.id, v2.id);
+        assert_eq!(v1.start_time, v2.start_time);
+        assert_eq!(v1.end_time, v2.end_time);
+    }
+
+    #[test]
+    fn unsupported_version_is_reported_distinctly() {
+        let err = decode(r#"{"schema_version":"99","duration":1.0,"start_time":"","id":"a","observations":[]}"#)
+            .unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedVersion>().is_some());
+    }
+}
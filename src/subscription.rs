@@ -0,0 +1,337 @@
+//! Fan-in for multiple MDB `(topic, source)` subscriptions.
+//!
+//! Each registered topic gets its own [`Handler`], but every subscription
+//! feeds a single bounded ring buffer, tagged with the topic it arrived on,
+//! so the rest of the program only has to drain one queue. The ring buffer
+//! never blocks the MDB callback: once full it sheds the oldest queued
+//! message and counts what it drops, rather than stalling or tearing down
+//! the subscription the way a `sync_channel` would.
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::CStr;
+use std::sync::{Arc, Condvar, Mutex};
+
+use log::{debug, warn};
+use mdb::{Connection, Subscriber, SubscriberConfig};
+
+/// How many payloads each topic's queue keeps before it has to start
+/// shedding load. Each registered topic gets its own queue of this size, so
+/// a burst on one topic can never evict another topic's queued messages.
+const RING_CAPACITY_PER_TOPIC: usize = 16;
+
+/// A raw payload, tagged with the topic it arrived on and, if the topic was
+/// registered with a coalesce key, the key used to collapse updates.
+struct TopicMessage {
+    topic: &'static CStr,
+    key: Option<String>,
+    payload: Result<String, std::string::FromUtf8Error>,
+}
+
+/// Decodes and acts on the payloads received on a single topic.
+pub trait Handler {
+    fn handle(&mut self, payload: &str) -> anyhow::Result<()>;
+}
+
+/// A topic's queued messages, plus how many have been dropped for that
+/// topic specifically.
+#[derive(Default)]
+struct TopicQueue {
+    messages: VecDeque<TopicMessage>,
+    dropped: u64,
+}
+
+/// One bounded queue per topic, sharing a single lock and condition
+/// variable so `pop` can block until *any* topic has work.
+///
+/// When a pushed message carries a coalesce key that matches one already
+/// queued on the same topic, it replaces that entry in place instead of
+/// taking a new slot. Otherwise, once that topic's queue is at
+/// [`RING_CAPACITY_PER_TOPIC`], the oldest message queued for it is dropped
+/// to make room, and the drop is counted against that topic alone — a burst
+/// on one topic shouldn't make another, perfectly healthy topic's first
+/// drop look like part of the same incident.
+struct RingBuffer {
+    queues: Mutex<HashMap<&'static CStr, TopicQueue>>,
+    not_empty: Condvar,
+}
+
+impl RingBuffer {
+    fn new() -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+            not_empty: Condvar::new(),
+        }
+    }
+
+    fn push(&self, message: TopicMessage) {
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(message.topic).or_default();
+
+        if let Some(key) = message.key.as_deref() {
+            if let Some(slot) = queue
+                .messages
+                .iter_mut()
+                .find(|queued| queued.key.as_deref() == Some(key))
+            {
+                *slot = message;
+                self.not_empty.notify_one();
+                return;
+            }
+        }
+
+        if queue.messages.len() == RING_CAPACITY_PER_TOPIC {
+            queue.messages.pop_front();
+            queue.dropped += 1;
+            // Cheap way to log "we're shedding load" without a timer thread:
+            // warn with decreasing frequency as the count grows.
+            if queue.dropped.is_power_of_two() {
+                let (dropped, topic) = (queue.dropped, message.topic);
+                warn!(
+                    "Dropped {dropped} message(s) so far because the consumer is falling behind on {topic:?}"
+                );
+            }
+        }
+        queue.messages.push_back(message);
+        self.not_empty.notify_one();
+    }
+
+    fn pop(&self) -> TopicMessage {
+        let mut queues = self.queues.lock().unwrap();
+        loop {
+            if let Some(message) = queues.values_mut().find_map(|queue| queue.messages.pop_front()) {
+                return message;
+            }
+            queues = self.not_empty.wait(queues).unwrap();
+        }
+    }
+
+    /// How many messages have been dropped in total, across every topic.
+    fn dropped(&self) -> u64 {
+        self.queues.lock().unwrap().values().map(|queue| queue.dropped).sum()
+    }
+
+    /// How many messages have been dropped for `topic` specifically.
+    fn dropped_for(&self, topic: &CStr) -> u64 {
+        self.queues
+            .lock()
+            .unwrap()
+            .get(topic)
+            .map(|queue| queue.dropped)
+            .unwrap_or(0)
+    }
+}
+
+/// Subscribes to several `(topic, source)` pairs and dispatches every
+/// incoming message to the [`Handler`] registered for its topic.
+pub struct SubscriptionManager<'a> {
+    connection: &'a Connection,
+    subscribers: Vec<Subscriber<'a>>,
+    handlers: HashMap<&'static CStr, Box<dyn Handler>>,
+    ring: Arc<RingBuffer>,
+}
+
+impl<'a> SubscriptionManager<'a> {
+    pub fn new(connection: &'a Connection) -> Self {
+        Self {
+            connection,
+            subscribers: Vec::new(),
+            handlers: HashMap::new(),
+            ring: Arc::new(RingBuffer::new()),
+        }
+    }
+
+    /// Registers `handler` to run on every message received on `(topic, source)`.
+    pub fn register(
+        &mut self,
+        topic: &'static CStr,
+        source: &CStr,
+        handler: Box<dyn Handler>,
+    ) -> anyhow::Result<()> {
+        self.register_coalescing(topic, source, handler, None)
+    }
+
+    /// Like [`Self::register`], but a message for which `key_fn` returns a key
+    /// already held by a queued message replaces it in place, rather than
+    /// contending with it for a slot in the ring buffer. Useful for topics
+    /// like `consolidated_track`, where a burst of updates for one track
+    /// shouldn't be able to shed updates for another.
+    pub fn register_coalescing(
+        &mut self,
+        topic: &'static CStr,
+        source: &CStr,
+        handler: Box<dyn Handler>,
+        key_fn: Option<Box<dyn Fn(&str) -> Option<String> + Send>>,
+    ) -> anyhow::Result<()> {
+        let ring = Arc::clone(&self.ring);
+        let config = SubscriberConfig::try_new(
+            topic,
+            source,
+            Box::new(move |message| {
+                let payload = String::from_utf8(message.payload().to_vec());
+                let key = match (&key_fn, &payload) {
+                    (Some(key_fn), Ok(payload)) => key_fn(payload),
+                    _ => None,
+                };
+                ring.push(TopicMessage {
+                    topic,
+                    key,
+                    payload,
+                });
+            }),
+        )
+        .map_err(|e| anyhow::anyhow!("Could not configure subscription to {topic:?}: {e:?}"))?;
+        let subscriber = Subscriber::try_new(
+            self.connection,
+            config,
+            Box::new(move |e| match e {
+                None => debug!("Subscribed to {topic:?}"),
+                Some(e) => warn!("Not subscribed to {topic:?} because {e:?}"),
+            }),
+        )
+        .map_err(|e| anyhow::anyhow!("Could not subscribe to {topic:?}: {e:?}"))?;
+        self.subscribers.push(subscriber);
+        self.handlers.insert(topic, handler);
+        Ok(())
+    }
+
+    /// How many messages have been shed, across every topic, because the
+    /// consumer fell behind.
+    pub fn dropped_count(&self) -> u64 {
+        self.ring.dropped()
+    }
+
+    /// How many messages have been shed for `topic` specifically.
+    pub fn dropped_count_for(&self, topic: &CStr) -> u64 {
+        self.ring.dropped_for(topic)
+    }
+
+    /// Drains the ring buffer, dispatching each message to the handler
+    /// registered for its topic. Runs until the process is killed: MDB
+    /// subscriptions, and therefore this loop, are expected to run for the
+    /// lifetime of the program.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        /// How often to surface `dropped_count()` as a heartbeat, so a slow
+        /// decline (well under the power-of-two threshold `RingBuffer::push`
+        /// warns at) is still visible somewhere.
+        const LOG_DROPPED_EVERY: u64 = 256;
+
+        let mut processed: u64 = 0;
+        loop {
+            let TopicMessage { topic, payload, .. } = self.ring.pop();
+            processed += 1;
+            if processed % LOG_DROPPED_EVERY == 0 {
+                debug!(
+                    "Processed {processed} message(s) so far, {} dropped overall ({} on {topic:?})",
+                    self.dropped_count(),
+                    self.dropped_count_for(topic)
+                );
+            }
+
+            let payload = match payload {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Could not read payload on {topic:?} because {e:?}");
+                    continue;
+                }
+            };
+            let Some(handler) = self.handlers.get_mut(topic) else {
+                warn!("No handler registered for {topic:?}");
+                continue;
+            };
+            if let Err(e) = handler.handle(&payload) {
+                warn!("Handler for {topic:?} failed: {e:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(topic: &'static CStr, key: Option<&str>, payload: &str) -> TopicMessage {
+        TopicMessage {
+            topic,
+            key: key.map(String::from),
+            payload: Ok(payload.to_string()),
+        }
+    }
+
+    fn payload_of(message: TopicMessage) -> String {
+        message.payload.unwrap()
+    }
+
+    #[test]
+    fn evicts_oldest_when_a_topics_queue_is_full() {
+        let ring = RingBuffer::new();
+        let topic = c"test.topic";
+        for i in 0..=RING_CAPACITY_PER_TOPIC {
+            ring.push(message(topic, None, &i.to_string()));
+        }
+        assert_eq!(ring.dropped(), 1);
+        // Message "0" was the oldest and should have been evicted.
+        assert_eq!(payload_of(ring.pop()), "1");
+    }
+
+    #[test]
+    fn coalesces_same_key_in_place_preserving_queue_order() {
+        let ring = RingBuffer::new();
+        let topic = c"test.topic";
+        ring.push(message(topic, Some("a"), "a-first"));
+        ring.push(message(topic, Some("b"), "b-only"));
+        ring.push(message(topic, Some("a"), "a-second"));
+
+        assert_eq!(ring.dropped(), 0);
+        // "a" kept its original queue position, but with the newer payload.
+        assert_eq!(payload_of(ring.pop()), "a-second");
+        assert_eq!(payload_of(ring.pop()), "b-only");
+    }
+
+    #[test]
+    fn drops_are_counted_per_topic_not_globally() {
+        let ring = RingBuffer::new();
+        let busy = c"test.topic.busy";
+        let quiet = c"test.topic.quiet";
+
+        for i in 0..=RING_CAPACITY_PER_TOPIC {
+            ring.push(message(busy, None, &i.to_string()));
+        }
+        assert_eq!(ring.dropped_for(busy), 1);
+        assert_eq!(ring.dropped_for(quiet), 0);
+
+        ring.push(message(quiet, None, "first-ever-message-on-quiet"));
+        // `quiet`'s own queue isn't full, so pushing to it shouldn't count
+        // as a drop for `quiet` just because `busy` has dropped messages.
+        assert_eq!(ring.dropped_for(quiet), 0);
+        assert_eq!(ring.dropped(), 1);
+    }
+
+    #[test]
+    fn one_topics_burst_does_not_evict_another_topics_messages() {
+        let ring = RingBuffer::new();
+        let busy = c"test.topic.busy";
+        let quiet = c"test.topic.quiet";
+
+        ring.push(message(quiet, None, "quiet-message"));
+        for i in 0..RING_CAPACITY_PER_TOPIC * 2 {
+            ring.push(message(busy, None, &i.to_string()));
+        }
+
+        // The busy topic sheds its own oldest messages; the quiet topic's
+        // single message must survive regardless of pop order between topics.
+        assert_eq!(ring.dropped(), RING_CAPACITY_PER_TOPIC as u64);
+        assert_eq!(ring.dropped_for(busy), RING_CAPACITY_PER_TOPIC as u64);
+        assert_eq!(ring.dropped_for(quiet), 0);
+        let popped: Vec<String> = std::iter::from_fn(|| {
+            let mut queues = ring.queues.lock().unwrap();
+            if queues.values().all(|queue| queue.messages.is_empty()) {
+                return None;
+            }
+            drop(queues);
+            Some(payload_of(ring.pop()))
+        })
+        .collect();
+        assert!(popped.contains(&"quiet-message".to_string()));
+        assert_eq!(popped.len(), 1 + RING_CAPACITY_PER_TOPIC);
+    }
+}
@@ -0,0 +1,71 @@
+//! Minimal RFC 3339 parsing, just enough for the MDB timestamp strings we see
+//! on the wire (e.g. `2024-01-01T12:00:00.123456789Z`). Not a general-purpose
+//! parser: no offsets other than `Z`, no leap seconds.
+
+const DAYS_PER_400_YEARS: i64 = 146_097;
+
+/// Parses an RFC 3339 timestamp into seconds since the Unix epoch.
+///
+/// Returns `None` on anything that doesn't look like `YYYY-MM-DDTHH:MM:SS[.frac]Z`.
+pub fn parse_unix_secs(s: &str) -> Option<f64> {
+    let s = s.strip_suffix('Z')?;
+    let (date, time) = s.split_once('T')?;
+
+    let mut date_parts = date.split('-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: i64 = date_parts.next()?.parse().ok()?;
+    let day: i64 = date_parts.next()?.parse().ok()?;
+
+    let (time, frac) = match time.split_once('.') {
+        Some((t, f)) => (t, Some(f)),
+        None => (time, None),
+    };
+    let mut time_parts = time.split(':');
+    let hour: i64 = time_parts.next()?.parse().ok()?;
+    let minute: i64 = time_parts.next()?.parse().ok()?;
+    let second: i64 = time_parts.next()?.parse().ok()?;
+    let nanos: f64 = match frac {
+        Some(f) => {
+            let digits: f64 = f.parse().ok()?;
+            digits / 10f64.powi(f.len() as i32)
+        }
+        None => 0.0,
+    };
+
+    let days = days_from_civil(year, month, day);
+    let secs_of_day = hour * 3600 + minute * 60 + second;
+    Some((days * 86_400 + secs_of_day) as f64 + nanos)
+}
+
+/// Days since the Unix epoch for a given proleptic-Gregorian date.
+/// Port of Howard Hinnant's well-known `days_from_civil` algorithm.
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * DAYS_PER_400_YEARS + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_epoch() {
+        assert_eq!(parse_unix_secs("1970-01-01T00:00:00Z"), Some(0.0));
+    }
+
+    #[test]
+    fn parses_fractional_seconds() {
+        let secs = parse_unix_secs("1970-01-01T00:00:00.5Z").unwrap();
+        assert!((secs - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_missing_zulu() {
+        assert_eq!(parse_unix_secs("1970-01-01T00:00:00"), None);
+    }
+}